@@ -0,0 +1,473 @@
+// Operation-based CRDT change log backing `DiagramEngine`.
+//
+// Every mutation is recorded as an immutable `Change` referencing the
+// hashes of the changes it was made on top of (`deps`), then applied to
+// the diagram through the same path used for merges so local and remote
+// writes resolve conflicts identically. Merging two logs means applying
+// the changes the other side doesn't have yet, in dependency order, and
+// resolving conflicts deterministically so both sides converge to the
+// same diagram regardless of merge order.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{Connector, DiagramSettings, Shape};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Op {
+    AddShape { shape: Shape },
+    UpdateShape { shape_id: String, fields: serde_json::Value },
+    DeleteShape { shape_id: String },
+    AddConnector { connector: Connector },
+    DeleteConnector { connector_id: String },
+    UpdateSettings { settings: DiagramSettings },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Change {
+    pub actor_id: String,
+    pub seq: u64,
+    pub timestamp: u64,
+    pub deps: Vec<String>,
+    pub op: Op,
+}
+
+fn hash_change(change: &Change) -> String {
+    let bytes = serde_json::to_vec(change).expect("Change always serializes");
+    let digest = Sha256::digest(&bytes);
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// A clock used for last-writer-wins conflict resolution: the change with
+/// the higher `(timestamp, actor_id)` pair wins, actor id breaking ties so
+/// resolution is deterministic across replicas.
+type Clock = (u64, String);
+
+fn clock_of(change: &Change) -> Clock {
+    (change.timestamp, change.actor_id.clone())
+}
+
+/// Append-only, mergeable log of `Change`s plus the derived state that
+/// falls out of replaying them: shape/connector tombstones and the
+/// per-field LWW clocks needed to apply changes deterministically.
+#[derive(Default)]
+pub struct ChangeLog {
+    actor_id: String,
+    seq: u64,
+    lamport: u64,
+    changes: HashMap<String, Change>,
+    heads: Vec<String>,
+    shape_tombstones: HashSet<String>,
+    connector_tombstones: HashSet<String>,
+    field_clocks: HashMap<String, HashMap<String, Clock>>,
+    settings_clock: Option<Clock>,
+}
+
+impl ChangeLog {
+    pub fn new(actor_id: String) -> Self {
+        ChangeLog {
+            actor_id,
+            ..Default::default()
+        }
+    }
+
+    /// Current frontier, sorted so two replicas with the same causal
+    /// state always report identical heads regardless of merge order.
+    pub fn heads(&self) -> Vec<String> {
+        let mut heads = self.heads.clone();
+        heads.sort();
+        heads
+    }
+
+    pub fn is_shape_deleted(&self, shape_id: &str) -> bool {
+        self.shape_tombstones.contains(shape_id)
+    }
+
+    pub fn is_connector_deleted(&self, connector_id: &str) -> bool {
+        self.connector_tombstones.contains(connector_id)
+    }
+
+    /// Record a local op and immediately apply it to the diagram through
+    /// the same path a merged remote change would take. Returns the hash
+    /// of the new change (the new head).
+    pub fn record_local(
+        &mut self,
+        op: Op,
+        shapes: &mut Vec<Shape>,
+        connectors: &mut Vec<Connector>,
+        settings: &mut DiagramSettings,
+    ) -> String {
+        self.seq += 1;
+        self.lamport += 1;
+        let change = Change {
+            actor_id: self.actor_id.clone(),
+            seq: self.seq,
+            timestamp: self.lamport,
+            deps: self.heads.clone(),
+            op,
+        };
+        let hash = self.insert_change(change.clone());
+        self.apply(&change, shapes, connectors, settings);
+        hash
+    }
+
+    /// Insert a change into the log (idempotent) and advance the frontier,
+    /// without applying it to any diagram state.
+    fn insert_change(&mut self, change: Change) -> String {
+        let hash = hash_change(&change);
+        if self.changes.contains_key(&hash) {
+            return hash;
+        }
+        self.lamport = self.lamport.max(change.timestamp);
+        // The new change supersedes any of its deps in the frontier; any
+        // other existing head is untouched (it's concurrent).
+        self.heads.retain(|h| !change.deps.contains(h));
+        if !self.heads.contains(&hash) {
+            self.heads.push(hash.clone());
+        }
+        self.changes.insert(hash.clone(), change);
+        hash
+    }
+
+    /// All hashes reachable (transitively, via `deps`) from `from`.
+    fn ancestors(&self, from: &[String]) -> HashSet<String> {
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut stack: Vec<String> = from.to_vec();
+        while let Some(hash) = stack.pop() {
+            if !seen.insert(hash.clone()) {
+                continue;
+            }
+            if let Some(change) = self.changes.get(&hash) {
+                for dep in &change.deps {
+                    stack.push(dep.clone());
+                }
+            }
+        }
+        seen
+    }
+
+    /// Changes the other side doesn't have yet (not in the causal history
+    /// of `since_heads`), topologically sorted so deps precede dependents.
+    pub fn changes_since(&self, since_heads: &[String]) -> Vec<Change> {
+        let known = self.ancestors(since_heads);
+        let pending: Vec<&String> = self
+            .changes
+            .keys()
+            .filter(|h| !known.contains(*h))
+            .collect();
+        let pending_set: HashSet<String> = pending.iter().map(|h| (*h).clone()).collect();
+
+        let mut in_degree: HashMap<String, usize> = HashMap::new();
+        let mut dependents: HashMap<String, Vec<String>> = HashMap::new();
+        for hash in &pending {
+            let change = &self.changes[*hash];
+            let local_deps = change.deps.iter().filter(|d| pending_set.contains(*d)).count();
+            in_degree.insert((*hash).clone(), local_deps);
+            for dep in &change.deps {
+                if pending_set.contains(dep) {
+                    dependents.entry(dep.clone()).or_default().push((*hash).clone());
+                }
+            }
+        }
+
+        let mut ready: Vec<String> = in_degree
+            .iter()
+            .filter(|(_, deg)| **deg == 0)
+            .map(|(h, _)| h.clone())
+            .collect();
+        ready.sort();
+
+        let mut ordered = Vec::with_capacity(pending.len());
+        while let Some(hash) = ready.pop() {
+            ordered.push(self.changes[&hash].clone());
+            if let Some(children) = dependents.get(&hash) {
+                for child in children {
+                    let deg = in_degree.get_mut(child).unwrap();
+                    *deg -= 1;
+                    if *deg == 0 {
+                        ready.push(child.clone());
+                    }
+                }
+            }
+            ready.sort();
+        }
+        ordered
+    }
+
+    /// Merge remote changes into this log, applying each in dependency
+    /// order. Returns the new heads.
+    pub fn merge_changes(
+        &mut self,
+        remote: Vec<Change>,
+        shapes: &mut Vec<Shape>,
+        connectors: &mut Vec<Connector>,
+        settings: &mut DiagramSettings,
+    ) -> Vec<String> {
+        // `remote` may not be topologically sorted (e.g. if the caller
+        // concatenated logs from multiple peers), so sort defensively by
+        // repeatedly taking changes whose deps are already known.
+        let mut pending = remote;
+        let mut progressed = true;
+        while progressed && !pending.is_empty() {
+            progressed = false;
+            let mut next_round = Vec::new();
+            for change in pending {
+                let hash = hash_change(&change);
+                let deps_known = self.changes.contains_key(&hash)
+                    || change.deps.iter().all(|d| self.changes.contains_key(d));
+                if deps_known {
+                    if !self.changes.contains_key(&hash) {
+                        self.insert_change(change.clone());
+                        self.apply(&change, shapes, connectors, settings);
+                    }
+                    progressed = true;
+                } else {
+                    next_round.push(change);
+                }
+            }
+            pending = next_round;
+        }
+        self.heads()
+    }
+
+    fn apply(
+        &mut self,
+        change: &Change,
+        shapes: &mut Vec<Shape>,
+        connectors: &mut Vec<Connector>,
+        settings: &mut DiagramSettings,
+    ) {
+        let clock = clock_of(change);
+        match &change.op {
+            Op::AddShape { shape } => {
+                if !self.shape_tombstones.contains(&shape.id)
+                    && !shapes.iter().any(|s| s.id == shape.id)
+                {
+                    shapes.push(shape.clone());
+                }
+            }
+            Op::UpdateShape { shape_id, fields } => {
+                if self.shape_tombstones.contains(shape_id) {
+                    return;
+                }
+                if let Some(shape) = shapes.iter_mut().find(|s| &s.id == shape_id) {
+                    apply_shape_fields(shape, fields, shape_id, &clock, &mut self.field_clocks);
+                }
+            }
+            Op::DeleteShape { shape_id } => {
+                self.shape_tombstones.insert(shape_id.clone());
+                shapes.retain(|s| &s.id != shape_id);
+                connectors.retain(|c| &c.from_shape_id != shape_id && &c.to_shape_id != shape_id);
+            }
+            Op::AddConnector { connector } => {
+                if !self.connector_tombstones.contains(&connector.id)
+                    && !self.shape_tombstones.contains(&connector.from_shape_id)
+                    && !self.shape_tombstones.contains(&connector.to_shape_id)
+                    && !connectors.iter().any(|c| c.id == connector.id)
+                {
+                    connectors.push(connector.clone());
+                }
+            }
+            Op::DeleteConnector { connector_id } => {
+                self.connector_tombstones.insert(connector_id.clone());
+                connectors.retain(|c| &c.id != connector_id);
+            }
+            Op::UpdateSettings { settings: new_settings } => {
+                let wins = match &self.settings_clock {
+                    Some(current) => clock > *current,
+                    None => true,
+                };
+                if wins {
+                    *settings = new_settings.clone();
+                    self.settings_clock = Some(clock);
+                }
+            }
+        }
+    }
+}
+
+/// Apply a partial field update to `shape`, keeping only the field whose
+/// clock is greater (last-writer-wins per field, not per change).
+fn apply_shape_fields(
+    shape: &mut Shape,
+    fields: &serde_json::Value,
+    shape_id: &str,
+    clock: &Clock,
+    field_clocks: &mut HashMap<String, HashMap<String, Clock>>,
+) {
+    let clocks = field_clocks.entry(shape_id.to_string()).or_default();
+    let mut wins = |field: &str| -> bool {
+        let should_win = match clocks.get(field) {
+            Some(current) => clock > current,
+            None => true,
+        };
+        if should_win {
+            clocks.insert(field.to_string(), clock.clone());
+        }
+        should_win
+    };
+
+    if let Some(x) = fields.get("x").and_then(|v| v.as_f64()) {
+        if wins("x") {
+            shape.x = x;
+        }
+    }
+    if let Some(y) = fields.get("y").and_then(|v| v.as_f64()) {
+        if wins("y") {
+            shape.y = y;
+        }
+    }
+    if let Some(width) = fields.get("width").and_then(|v| v.as_f64()) {
+        if wins("width") {
+            shape.width = width;
+        }
+    }
+    if let Some(height) = fields.get("height").and_then(|v| v.as_f64()) {
+        if wins("height") {
+            shape.height = height;
+        }
+    }
+    if let Some(rotation) = fields.get("rotation").and_then(|v| v.as_f64()) {
+        if wins("rotation") {
+            shape.rotation = rotation;
+        }
+    }
+    if let Some(fill) = fields.get("fill").and_then(|v| v.as_str()) {
+        if wins("fill") {
+            shape.fill = fill.to_string();
+        }
+    }
+    if let Some(stroke) = fields.get("stroke").and_then(|v| v.as_str()) {
+        if wins("stroke") {
+            shape.stroke = stroke.to_string();
+        }
+    }
+    if let Some(stroke_width) = fields.get("strokeWidth").and_then(|v| v.as_f64()) {
+        if wins("strokeWidth") {
+            shape.stroke_width = stroke_width;
+        }
+    }
+    if let Some(text) = fields.get("text").and_then(|v| v.as_str()) {
+        if wins("text") {
+            shape.text = Some(text.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shape(id: &str) -> Shape {
+        Shape {
+            id: id.to_string(),
+            shape_type: crate::ShapeType::Rectangle,
+            x: 0.0,
+            y: 0.0,
+            width: 10.0,
+            height: 10.0,
+            rotation: 0.0,
+            fill: "#000".to_string(),
+            stroke: "#000".to_string(),
+            stroke_width: 1.0,
+            text: None,
+        }
+    }
+
+    #[test]
+    fn concurrent_updates_converge_via_last_writer_wins() {
+        let mut a = ChangeLog::new("a".to_string());
+        let mut a_shapes = Vec::new();
+        let mut a_connectors = Vec::new();
+        let mut a_settings = DiagramSettings::default();
+        a.record_local(
+            Op::AddShape { shape: shape("s1") },
+            &mut a_shapes,
+            &mut a_connectors,
+            &mut a_settings,
+        );
+
+        let mut b = ChangeLog::new("b".to_string());
+        let mut b_shapes = Vec::new();
+        let mut b_connectors = Vec::new();
+        let mut b_settings = DiagramSettings::default();
+        b.merge_changes(
+            a.changes_since(&[]),
+            &mut b_shapes,
+            &mut b_connectors,
+            &mut b_settings,
+        );
+
+        let base_heads = a.heads();
+        a.record_local(
+            Op::UpdateShape {
+                shape_id: "s1".to_string(),
+                fields: serde_json::json!({ "x": 5.0 }),
+            },
+            &mut a_shapes,
+            &mut a_connectors,
+            &mut a_settings,
+        );
+        b.record_local(
+            Op::UpdateShape {
+                shape_id: "s1".to_string(),
+                fields: serde_json::json!({ "x": 9.0 }),
+            },
+            &mut b_shapes,
+            &mut b_connectors,
+            &mut b_settings,
+        );
+
+        let a_new = a.changes_since(&base_heads);
+        let b_new = b.changes_since(&base_heads);
+
+        a.merge_changes(b_new, &mut a_shapes, &mut a_connectors, &mut a_settings);
+        b.merge_changes(a_new, &mut b_shapes, &mut b_connectors, &mut b_settings);
+
+        assert_eq!(a_shapes[0].x, b_shapes[0].x);
+        assert_eq!(a.heads(), b.heads());
+    }
+
+    #[test]
+    fn delete_wins_over_concurrent_update() {
+        let mut log = ChangeLog::new("a".to_string());
+        let mut shapes = Vec::new();
+        let mut connectors = Vec::new();
+        let mut settings = DiagramSettings::default();
+        log.record_local(
+            Op::AddShape { shape: shape("s1") },
+            &mut shapes,
+            &mut connectors,
+            &mut settings,
+        );
+        log.record_local(
+            Op::DeleteShape { shape_id: "s1".to_string() },
+            &mut shapes,
+            &mut connectors,
+            &mut settings,
+        );
+
+        log.merge_changes(
+            vec![Change {
+                actor_id: "b".to_string(),
+                seq: 1,
+                timestamp: 1,
+                deps: vec![],
+                op: Op::UpdateShape {
+                    shape_id: "s1".to_string(),
+                    fields: serde_json::json!({ "x": 99.0 }),
+                },
+            }],
+            &mut shapes,
+            &mut connectors,
+            &mut settings,
+        );
+
+        assert!(shapes.is_empty());
+        assert!(log.is_shape_deleted("s1"));
+    }
+}
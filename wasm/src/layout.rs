@@ -0,0 +1,364 @@
+// Automatic layout for connected shapes, driven by the directed graph
+// connectors already define over shapes.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::{Connector, DiagramSettings, Shape};
+
+const DEFAULT_LAYER_SPACING: f64 = 150.0;
+const DEFAULT_NODE_SPACING: f64 = 120.0;
+const FORCE_ITERATIONS: usize = 200;
+const REPULSION_STRENGTH: f64 = 6000.0;
+const SPRING_STRENGTH: f64 = 0.02;
+const SPRING_LENGTH: f64 = 160.0;
+const STEP_SIZE: f64 = 0.6;
+
+/// Reposition `shapes` in place using `algorithm` ("layered" or
+/// "force-directed"), then snap final coordinates to the grid if enabled.
+pub fn auto_layout(
+    algorithm: &str,
+    shapes: &mut [Shape],
+    connectors: &[Connector],
+    settings: &DiagramSettings,
+) -> Result<(), String> {
+    let (connected, disconnected) = partition_connected(shapes, connectors);
+
+    let mut positions: HashMap<String, (f64, f64)> = match algorithm {
+        "layered" => layered_positions(&connected, connectors),
+        "force-directed" => force_directed_positions(&connected, connectors),
+        other => return Err(format!("unknown layout algorithm: {}", other)),
+    };
+
+    // Disconnected shapes go in their own trailing row below everything
+    // the algorithm placed, left-to-right in their existing order.
+    let trailing_y = positions
+        .values()
+        .map(|(_, y)| *y)
+        .fold(f64::MIN, f64::max)
+        .max(0.0)
+        + DEFAULT_LAYER_SPACING;
+    for (i, id) in disconnected.iter().enumerate() {
+        positions.insert(id.clone(), (i as f64 * DEFAULT_NODE_SPACING, trailing_y));
+    }
+
+    for shape in shapes.iter_mut() {
+        if let Some((x, y)) = positions.get(&shape.id) {
+            let (x, y) = if settings.snap_to_grid {
+                snap(*x, *y, settings.grid_size)
+            } else {
+                (*x, *y)
+            };
+            shape.x = x;
+            shape.y = y;
+        }
+    }
+
+    Ok(())
+}
+
+fn snap(x: f64, y: f64, grid: f64) -> (f64, f64) {
+    ((x / grid).round() * grid, (y / grid).round() * grid)
+}
+
+/// Split shape ids into those touched by at least one connector and the
+/// rest, preserving each group's original relative order.
+fn partition_connected(shapes: &[Shape], connectors: &[Connector]) -> (Vec<String>, Vec<String>) {
+    let touched: HashSet<&str> = connectors
+        .iter()
+        .flat_map(|c| [c.from_shape_id.as_str(), c.to_shape_id.as_str()])
+        .collect();
+    let mut connected = Vec::new();
+    let mut disconnected = Vec::new();
+    for shape in shapes {
+        if touched.contains(shape.id.as_str()) {
+            connected.push(shape.id.clone());
+        } else {
+            disconnected.push(shape.id.clone());
+        }
+    }
+    (connected, disconnected)
+}
+
+fn adjacency(ids: &[String], connectors: &[Connector]) -> HashMap<String, Vec<String>> {
+    let id_set: HashSet<&str> = ids.iter().map(|s| s.as_str()).collect();
+    let mut out: HashMap<String, Vec<String>> = ids.iter().map(|id| (id.clone(), Vec::new())).collect();
+    for connector in connectors {
+        if id_set.contains(connector.from_shape_id.as_str())
+            && id_set.contains(connector.to_shape_id.as_str())
+        {
+            out.entry(connector.from_shape_id.clone())
+                .or_default()
+                .push(connector.to_shape_id.clone());
+        }
+    }
+    out
+}
+
+/// Layered (Sugiyama-style) layout for DAGs: longest-path ranking from
+/// source nodes assigns layers, then a few barycenter sweeps reorder
+/// nodes within each layer to reduce edge crossings.
+fn layered_positions(ids: &[String], connectors: &[Connector]) -> HashMap<String, (f64, f64)> {
+    if ids.is_empty() {
+        return HashMap::new();
+    }
+    let out_edges = adjacency(ids, connectors);
+    let mut in_edges: HashMap<String, Vec<String>> = ids.iter().map(|id| (id.clone(), Vec::new())).collect();
+    for (from, tos) in &out_edges {
+        for to in tos {
+            in_edges.get_mut(to).unwrap().push(from.clone());
+        }
+    }
+
+    let layer = longest_path_layers(ids, &out_edges, &in_edges);
+    let max_layer = layer.values().copied().max().unwrap_or(0);
+
+    let mut layers: Vec<Vec<String>> = vec![Vec::new(); max_layer + 1];
+    for id in ids {
+        layers[layer[id]].push(id.clone());
+    }
+
+    // Barycenter sweeps: a few passes averaging each node's position from
+    // its neighbors in the adjacent layer, alternating direction.
+    const SWEEPS: usize = 4;
+    for sweep in 0..SWEEPS {
+        let downward = sweep % 2 == 0;
+        let range: Box<dyn Iterator<Item = usize>> = if downward {
+            Box::new(1..layers.len())
+        } else {
+            Box::new((0..layers.len().saturating_sub(1)).rev())
+        };
+        for i in range {
+            let neighbor_layer = if downward { i - 1 } else { i + 1 };
+            let position_in: HashMap<&str, usize> = layers[neighbor_layer]
+                .iter()
+                .enumerate()
+                .map(|(pos, id)| (id.as_str(), pos))
+                .collect();
+            let edges = if downward { &in_edges } else { &out_edges };
+            let mut scored: Vec<(f64, String)> = layers[i]
+                .iter()
+                .map(|id| {
+                    let neighbor_positions: Vec<f64> = edges[id]
+                        .iter()
+                        .filter_map(|n| position_in.get(n.as_str()))
+                        .map(|p| *p as f64)
+                        .collect();
+                    let score = if neighbor_positions.is_empty() {
+                        position_in.len() as f64 / 2.0
+                    } else {
+                        neighbor_positions.iter().sum::<f64>() / neighbor_positions.len() as f64
+                    };
+                    (score, id.clone())
+                })
+                .collect();
+            scored.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+            layers[i] = scored.into_iter().map(|(_, id)| id).collect();
+        }
+    }
+
+    let mut positions = HashMap::new();
+    for (layer_index, nodes) in layers.iter().enumerate() {
+        for (node_index, id) in nodes.iter().enumerate() {
+            positions.insert(
+                id.clone(),
+                (
+                    node_index as f64 * DEFAULT_NODE_SPACING,
+                    layer_index as f64 * DEFAULT_LAYER_SPACING,
+                ),
+            );
+        }
+    }
+    positions
+}
+
+/// Longest-path layering: each node's layer is one more than the deepest
+/// layer of its predecessors, computed in topological order so source
+/// nodes (no incoming edges) land on layer 0.
+fn longest_path_layers(
+    ids: &[String],
+    out_edges: &HashMap<String, Vec<String>>,
+    in_edges: &HashMap<String, Vec<String>>,
+) -> HashMap<String, usize> {
+    let mut in_degree: HashMap<String, usize> =
+        ids.iter().map(|id| (id.clone(), in_edges[id].len())).collect();
+    let mut queue: VecDeque<String> = in_degree
+        .iter()
+        .filter(|(_, deg)| **deg == 0)
+        .map(|(id, _)| id.clone())
+        .collect();
+
+    let mut layer: HashMap<String, usize> = ids.iter().map(|id| (id.clone(), 0)).collect();
+    // Nodes on a cycle (and nodes downstream of one) never reach
+    // in-degree 0 and are never processed: they keep layer 0, or one more
+    // than a processed predecessor's layer, and their own successors are
+    // not ranked from them. Longest-path ranking only applies to the DAG
+    // portion of the graph.
+    while let Some(node) = queue.pop_front() {
+        for next in &out_edges[&node] {
+            let candidate = layer[&node] + 1;
+            if candidate > layer[next] {
+                layer.insert(next.clone(), candidate);
+            }
+            let deg = in_degree.get_mut(next).unwrap();
+            *deg -= 1;
+            if *deg == 0 {
+                queue.push_back(next.clone());
+            }
+        }
+    }
+    layer
+}
+
+/// Force-directed layout: repulsion between every pair of shapes, an
+/// attractive spring along each connector, iterated to a fixed step
+/// count (no convergence check, matching a simple cooling-free model).
+fn force_directed_positions(ids: &[String], connectors: &[Connector]) -> HashMap<String, (f64, f64)> {
+    if ids.is_empty() {
+        return HashMap::new();
+    }
+    let id_set: HashSet<&str> = ids.iter().map(|s| s.as_str()).collect();
+    let edges: Vec<(String, String)> = connectors
+        .iter()
+        .filter(|c| id_set.contains(c.from_shape_id.as_str()) && id_set.contains(c.to_shape_id.as_str()))
+        .map(|c| (c.from_shape_id.clone(), c.to_shape_id.clone()))
+        .collect();
+
+    // Deterministic initial placement on a circle (no RNG available here).
+    let mut pos: HashMap<String, (f64, f64)> = ids
+        .iter()
+        .enumerate()
+        .map(|(i, id)| {
+            let angle = 2.0 * std::f64::consts::PI * i as f64 / ids.len() as f64;
+            (id.clone(), (200.0 * angle.cos(), 200.0 * angle.sin()))
+        })
+        .collect();
+
+    for _ in 0..FORCE_ITERATIONS {
+        let mut forces: HashMap<String, (f64, f64)> = ids.iter().map(|id| (id.clone(), (0.0, 0.0))).collect();
+
+        for i in 0..ids.len() {
+            for j in (i + 1)..ids.len() {
+                let (ax, ay) = pos[&ids[i]];
+                let (bx, by) = pos[&ids[j]];
+                let dx = ax - bx;
+                let dy = ay - by;
+                let dist_sq = (dx * dx + dy * dy).max(1.0);
+                let force = REPULSION_STRENGTH / dist_sq;
+                let dist = dist_sq.sqrt();
+                let (fx, fy) = (dx / dist * force, dy / dist * force);
+                let a = forces.get_mut(&ids[i]).unwrap();
+                a.0 += fx;
+                a.1 += fy;
+                let b = forces.get_mut(&ids[j]).unwrap();
+                b.0 -= fx;
+                b.1 -= fy;
+            }
+        }
+
+        for (from, to) in &edges {
+            let (ax, ay) = pos[from];
+            let (bx, by) = pos[to];
+            let dx = bx - ax;
+            let dy = by - ay;
+            let dist = (dx * dx + dy * dy).sqrt().max(1.0);
+            let stretch = dist - SPRING_LENGTH;
+            let force = SPRING_STRENGTH * stretch;
+            let (fx, fy) = (dx / dist * force, dy / dist * force);
+            let a = forces.get_mut(from).unwrap();
+            a.0 += fx;
+            a.1 += fy;
+            let b = forces.get_mut(to).unwrap();
+            b.0 -= fx;
+            b.1 -= fy;
+        }
+
+        for id in ids {
+            let (fx, fy) = forces[id];
+            let (x, y) = pos.get_mut(id).unwrap();
+            *x += fx * STEP_SIZE;
+            *y += fy * STEP_SIZE;
+        }
+    }
+
+    pos
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ShapeType;
+
+    fn shape(id: &str) -> Shape {
+        Shape {
+            id: id.to_string(),
+            shape_type: ShapeType::Rectangle,
+            x: 0.0,
+            y: 0.0,
+            width: 80.0,
+            height: 40.0,
+            rotation: 0.0,
+            fill: "#fff".to_string(),
+            stroke: "#000".to_string(),
+            stroke_width: 1.0,
+            text: None,
+        }
+    }
+
+    fn connector(id: &str, from: &str, to: &str) -> Connector {
+        Connector {
+            id: id.to_string(),
+            from_shape_id: from.to_string(),
+            to_shape_id: to.to_string(),
+            from_anchor: "right".to_string(),
+            to_anchor: "left".to_string(),
+            stroke: "#000".to_string(),
+            stroke_width: 1.0,
+        }
+    }
+
+    #[test]
+    fn layered_layout_orders_by_depth() {
+        let mut shapes = vec![shape("a"), shape("b"), shape("c")];
+        let connectors = vec![connector("c1", "a", "b"), connector("c2", "b", "c")];
+        let settings = DiagramSettings { snap_to_grid: false, ..DiagramSettings::default() };
+
+        auto_layout("layered", &mut shapes, &connectors, &settings).unwrap();
+
+        let by_id: HashMap<&str, &Shape> = shapes.iter().map(|s| (s.id.as_str(), s)).collect();
+        assert!(by_id["a"].y < by_id["b"].y);
+        assert!(by_id["b"].y < by_id["c"].y);
+    }
+
+    #[test]
+    fn disconnected_shapes_go_to_trailing_row() {
+        let mut shapes = vec![shape("a"), shape("b"), shape("isolated")];
+        let connectors = vec![connector("c1", "a", "b")];
+        let settings = DiagramSettings { snap_to_grid: false, ..DiagramSettings::default() };
+
+        auto_layout("layered", &mut shapes, &connectors, &settings).unwrap();
+
+        let by_id: HashMap<&str, &Shape> = shapes.iter().map(|s| (s.id.as_str(), s)).collect();
+        assert!(by_id["isolated"].y > by_id["a"].y);
+        assert!(by_id["isolated"].y > by_id["b"].y);
+    }
+
+    #[test]
+    fn force_directed_spreads_connected_shapes_apart() {
+        let mut shapes = vec![shape("a"), shape("b")];
+        let connectors = vec![connector("c1", "a", "b")];
+        let settings = DiagramSettings { snap_to_grid: false, ..DiagramSettings::default() };
+
+        auto_layout("force-directed", &mut shapes, &connectors, &settings).unwrap();
+
+        let dx = shapes[0].x - shapes[1].x;
+        let dy = shapes[0].y - shapes[1].y;
+        assert!((dx * dx + dy * dy).sqrt() > 1.0);
+    }
+
+    #[test]
+    fn unknown_algorithm_is_rejected() {
+        let mut shapes = vec![shape("a")];
+        let settings = DiagramSettings::default();
+        assert!(auto_layout("spiral", &mut shapes, &[], &settings).is_err());
+    }
+}
@@ -1,324 +1,594 @@
-use serde::{Deserialize, Serialize};
-use wasm_bindgen::prelude::*;
-
-// Initialize panic hook for better error messages
-#[wasm_bindgen(start)]
-pub fn init() {
-    #[cfg(feature = "console_error_panic_hook")]
-    console_error_panic_hook::set_once();
-}
-
-// Shape types
-#[derive(Serialize, Deserialize, Clone, Debug)]
-#[serde(rename_all = "camelCase")]
-pub enum ShapeType {
-    Rectangle,
-    Circle,
-    Diamond,
-    Text,
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct Shape {
-    pub id: String,
-    #[serde(rename = "type")]
-    pub shape_type: ShapeType,
-    pub x: f64,
-    pub y: f64,
-    pub width: f64,
-    pub height: f64,
-    pub rotation: f64,
-    pub fill: String,
-    pub stroke: String,
-    pub stroke_width: f64,
-    pub text: Option<String>,
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct Connector {
-    pub id: String,
-    pub from_shape_id: String,
-    pub to_shape_id: String,
-    pub from_anchor: String,
-    pub to_anchor: String,
-    pub stroke: String,
-    pub stroke_width: f64,
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct DiagramSettings {
-    pub background_color: String,
-    pub grid_enabled: bool,
-    pub snap_to_grid: bool,
-    pub grid_size: f64,
-}
-
-impl Default for DiagramSettings {
-    fn default() -> Self {
-        Self {
-            background_color: "#ffffff".to_string(),
-            grid_enabled: true,
-            snap_to_grid: true,
-            grid_size: 20.0,
-        }
-    }
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct Diagram {
-    pub id: String,
-    pub name: String,
-    pub shapes: Vec<Shape>,
-    pub connectors: Vec<Connector>,
-    pub settings: DiagramSettings,
-    pub created_at: String,
-    pub updated_at: String,
-}
-
-// WASM-exposed diagram engine
-#[wasm_bindgen]
-pub struct DiagramEngine {
-    diagram: Diagram,
-}
-
-#[wasm_bindgen]
-impl DiagramEngine {
-    #[wasm_bindgen(constructor)]
-    pub fn new(id: &str, name: &str) -> DiagramEngine {
-        let now = js_sys::Date::new_0().to_iso_string().as_string().unwrap();
-        DiagramEngine {
-            diagram: Diagram {
-                id: id.to_string(),
-                name: name.to_string(),
-                shapes: Vec::new(),
-                connectors: Vec::new(),
-                settings: DiagramSettings::default(),
-                created_at: now.clone(),
-                updated_at: now,
-            },
-        }
-    }
-
-    #[wasm_bindgen(js_name = fromJson)]
-    pub fn from_json(json: &str) -> Result<DiagramEngine, JsValue> {
-        let diagram: Diagram = serde_json::from_str(json)
-            .map_err(|e| JsValue::from_str(&format!("Parse error: {}", e)))?;
-        Ok(DiagramEngine { diagram })
-    }
-
-    #[wasm_bindgen(js_name = toJson)]
-    pub fn to_json(&self) -> Result<String, JsValue> {
-        serde_json::to_string(&self.diagram)
-            .map_err(|e| JsValue::from_str(&format!("Serialize error: {}", e)))
-    }
-
-    #[wasm_bindgen(js_name = addShape)]
-    pub fn add_shape(&mut self, shape_json: &str) -> Result<(), JsValue> {
-        let shape: Shape = serde_json::from_str(shape_json)
-            .map_err(|e| JsValue::from_str(&format!("Parse error: {}", e)))?;
-        self.diagram.shapes.push(shape);
-        self.update_timestamp();
-        Ok(())
-    }
-
-    #[wasm_bindgen(js_name = updateShape)]
-    pub fn update_shape(&mut self, shape_id: &str, updates_json: &str) -> Result<(), JsValue> {
-        let updates: serde_json::Value = serde_json::from_str(updates_json)
-            .map_err(|e| JsValue::from_str(&format!("Parse error: {}", e)))?;
-
-        if let Some(shape) = self.diagram.shapes.iter_mut().find(|s| s.id == shape_id) {
-            if let Some(x) = updates.get("x").and_then(|v| v.as_f64()) {
-                shape.x = x;
-            }
-            if let Some(y) = updates.get("y").and_then(|v| v.as_f64()) {
-                shape.y = y;
-            }
-            if let Some(width) = updates.get("width").and_then(|v| v.as_f64()) {
-                shape.width = width;
-            }
-            if let Some(height) = updates.get("height").and_then(|v| v.as_f64()) {
-                shape.height = height;
-            }
-            if let Some(rotation) = updates.get("rotation").and_then(|v| v.as_f64()) {
-                shape.rotation = rotation;
-            }
-            if let Some(fill) = updates.get("fill").and_then(|v| v.as_str()) {
-                shape.fill = fill.to_string();
-            }
-            if let Some(stroke) = updates.get("stroke").and_then(|v| v.as_str()) {
-                shape.stroke = stroke.to_string();
-            }
-            if let Some(stroke_width) = updates.get("strokeWidth").and_then(|v| v.as_f64()) {
-                shape.stroke_width = stroke_width;
-            }
-            if let Some(text) = updates.get("text").and_then(|v| v.as_str()) {
-                shape.text = Some(text.to_string());
-            }
-            self.update_timestamp();
-            Ok(())
-        } else {
-            Err(JsValue::from_str("Shape not found"))
-        }
-    }
-
-    #[wasm_bindgen(js_name = deleteShape)]
-    pub fn delete_shape(&mut self, shape_id: &str) -> Result<(), JsValue> {
-        let initial_len = self.diagram.shapes.len();
-        self.diagram.shapes.retain(|s| s.id != shape_id);
-
-        // Also remove connectors attached to this shape
-        self.diagram.connectors.retain(|c| {
-            c.from_shape_id != shape_id && c.to_shape_id != shape_id
-        });
-
-        if self.diagram.shapes.len() < initial_len {
-            self.update_timestamp();
-            Ok(())
-        } else {
-            Err(JsValue::from_str("Shape not found"))
-        }
-    }
-
-    #[wasm_bindgen(js_name = addConnector)]
-    pub fn add_connector(&mut self, connector_json: &str) -> Result<(), JsValue> {
-        let connector: Connector = serde_json::from_str(connector_json)
-            .map_err(|e| JsValue::from_str(&format!("Parse error: {}", e)))?;
-        self.diagram.connectors.push(connector);
-        self.update_timestamp();
-        Ok(())
-    }
-
-    #[wasm_bindgen(js_name = deleteConnector)]
-    pub fn delete_connector(&mut self, connector_id: &str) -> Result<(), JsValue> {
-        let initial_len = self.diagram.connectors.len();
-        self.diagram.connectors.retain(|c| c.id != connector_id);
-
-        if self.diagram.connectors.len() < initial_len {
-            self.update_timestamp();
-            Ok(())
-        } else {
-            Err(JsValue::from_str("Connector not found"))
-        }
-    }
-
-    #[wasm_bindgen(js_name = updateSettings)]
-    pub fn update_settings(&mut self, settings_json: &str) -> Result<(), JsValue> {
-        let settings: DiagramSettings = serde_json::from_str(settings_json)
-            .map_err(|e| JsValue::from_str(&format!("Parse error: {}", e)))?;
-        self.diagram.settings = settings;
-        self.update_timestamp();
-        Ok(())
-    }
-
-    #[wasm_bindgen(js_name = getShapes)]
-    pub fn get_shapes(&self) -> Result<String, JsValue> {
-        serde_json::to_string(&self.diagram.shapes)
-            .map_err(|e| JsValue::from_str(&format!("Serialize error: {}", e)))
-    }
-
-    #[wasm_bindgen(js_name = getConnectors)]
-    pub fn get_connectors(&self) -> Result<String, JsValue> {
-        serde_json::to_string(&self.diagram.connectors)
-            .map_err(|e| JsValue::from_str(&format!("Serialize error: {}", e)))
-    }
-
-    #[wasm_bindgen(js_name = getSettings)]
-    pub fn get_settings(&self) -> Result<String, JsValue> {
-        serde_json::to_string(&self.diagram.settings)
-            .map_err(|e| JsValue::from_str(&format!("Serialize error: {}", e)))
-    }
-
-    #[wasm_bindgen(js_name = snapToGrid)]
-    pub fn snap_to_grid(&self, x: f64, y: f64) -> Vec<f64> {
-        if self.diagram.settings.snap_to_grid {
-            let grid = self.diagram.settings.grid_size;
-            vec![
-                (x / grid).round() * grid,
-                (y / grid).round() * grid,
-            ]
-        } else {
-            vec![x, y]
-        }
-    }
-
-    #[wasm_bindgen(js_name = findShapeAt)]
-    pub fn find_shape_at(&self, x: f64, y: f64) -> Option<String> {
-        // Reverse iterate to find topmost shape
-        for shape in self.diagram.shapes.iter().rev() {
-            if x >= shape.x && x <= shape.x + shape.width &&
-               y >= shape.y && y <= shape.y + shape.height {
-                return Some(shape.id.clone());
-            }
-        }
-        None
-    }
-
-    fn update_timestamp(&mut self) {
-        self.diagram.updated_at = js_sys::Date::new_0().to_iso_string().as_string().unwrap();
-    }
-}
-
-// Utility functions
-#[wasm_bindgen(js_name = generateId)]
-pub fn generate_id() -> String {
-    uuid::Uuid::new_v4().to_string()
-}
-
-#[wasm_bindgen(js_name = createDefaultShape)]
-pub fn create_default_shape(shape_type: &str, x: f64, y: f64) -> Result<String, JsValue> {
-    let shape = Shape {
-        id: generate_id(),
-        shape_type: match shape_type {
-            "rectangle" => ShapeType::Rectangle,
-            "circle" => ShapeType::Circle,
-            "diamond" => ShapeType::Diamond,
-            "text" => ShapeType::Text,
-            _ => return Err(JsValue::from_str("Invalid shape type")),
-        },
-        x,
-        y,
-        width: 100.0,
-        height: 100.0,
-        rotation: 0.0,
-        fill: "#4f46e5".to_string(),
-        stroke: "#3730a3".to_string(),
-        stroke_width: 2.0,
-        text: if shape_type == "text" { Some("Text".to_string()) } else { None },
-    };
-
-    serde_json::to_string(&shape)
-        .map_err(|e| JsValue::from_str(&format!("Serialize error: {}", e)))
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_create_diagram() {
-        let engine = DiagramEngine::new("test-id", "Test Diagram");
-        let json = engine.to_json().unwrap();
-        assert!(json.contains("test-id"));
-        assert!(json.contains("Test Diagram"));
-    }
-
-    #[test]
-    fn test_add_shape() {
-        let mut engine = DiagramEngine::new("test-id", "Test");
-        let shape = create_default_shape("rectangle", 100.0, 100.0).unwrap();
-        engine.add_shape(&shape).unwrap();
-        let shapes = engine.get_shapes().unwrap();
-        assert!(shapes.contains("rectangle"));
-    }
-
-    #[test]
-    fn test_snap_to_grid() {
-        let engine = DiagramEngine::new("test-id", "Test");
-        let snapped = engine.snap_to_grid(25.0, 33.0);
-        assert_eq!(snapped, vec![20.0, 40.0]);
-    }
-}
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+mod crdt;
+mod layout;
+mod rdf;
+
+use crdt::{ChangeLog, Op};
+use rdf::TripleStore;
+
+// Initialize panic hook for better error messages
+#[wasm_bindgen(start)]
+pub fn init() {
+    #[cfg(feature = "console_error_panic_hook")]
+    console_error_panic_hook::set_once();
+}
+
+// Shape types
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum ShapeType {
+    Rectangle,
+    Circle,
+    Diamond,
+    Text,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Shape {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub shape_type: ShapeType,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub rotation: f64,
+    pub fill: String,
+    pub stroke: String,
+    pub stroke_width: f64,
+    pub text: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Connector {
+    pub id: String,
+    pub from_shape_id: String,
+    pub to_shape_id: String,
+    pub from_anchor: String,
+    pub to_anchor: String,
+    pub stroke: String,
+    pub stroke_width: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagramSettings {
+    pub background_color: String,
+    pub grid_enabled: bool,
+    pub snap_to_grid: bool,
+    pub grid_size: f64,
+}
+
+impl Default for DiagramSettings {
+    fn default() -> Self {
+        Self {
+            background_color: "#ffffff".to_string(),
+            grid_enabled: true,
+            snap_to_grid: true,
+            grid_size: 20.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagram {
+    pub id: String,
+    pub name: String,
+    pub shapes: Vec<Shape>,
+    pub connectors: Vec<Connector>,
+    pub settings: DiagramSettings,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+// WASM-exposed diagram engine
+#[wasm_bindgen]
+pub struct DiagramEngine {
+    diagram: Diagram,
+    log: ChangeLog,
+}
+
+#[wasm_bindgen]
+impl DiagramEngine {
+    #[wasm_bindgen(constructor)]
+    pub fn new(id: &str, name: &str, actor_id: Option<String>) -> DiagramEngine {
+        let now = js_sys::Date::new_0().to_iso_string().as_string().unwrap();
+        DiagramEngine {
+            diagram: Diagram {
+                id: id.to_string(),
+                name: name.to_string(),
+                shapes: Vec::new(),
+                connectors: Vec::new(),
+                settings: DiagramSettings::default(),
+                created_at: now.clone(),
+                updated_at: now,
+            },
+            log: ChangeLog::new(actor_id.unwrap_or_else(generate_id)),
+        }
+    }
+
+    #[wasm_bindgen(js_name = fromJson)]
+    pub fn from_json(json: &str, actor_id: Option<String>) -> Result<DiagramEngine, JsValue> {
+        let diagram: Diagram = serde_json::from_str(json)
+            .map_err(|e| JsValue::from_str(&format!("Parse error: {}", e)))?;
+        Ok(DiagramEngine {
+            diagram,
+            log: ChangeLog::new(actor_id.unwrap_or_else(generate_id)),
+        })
+    }
+
+    #[wasm_bindgen(js_name = toJson)]
+    pub fn to_json(&self) -> Result<String, JsValue> {
+        serde_json::to_string(&self.diagram)
+            .map_err(|e| JsValue::from_str(&format!("Serialize error: {}", e)))
+    }
+
+    #[wasm_bindgen(js_name = addShape)]
+    pub fn add_shape(&mut self, shape_json: &str) -> Result<(), JsValue> {
+        let shape: Shape = serde_json::from_str(shape_json)
+            .map_err(|e| JsValue::from_str(&format!("Parse error: {}", e)))?;
+        self.record(Op::AddShape { shape });
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = updateShape)]
+    pub fn update_shape(&mut self, shape_id: &str, updates_json: &str) -> Result<(), JsValue> {
+        let fields: serde_json::Value = serde_json::from_str(updates_json)
+            .map_err(|e| JsValue::from_str(&format!("Parse error: {}", e)))?;
+
+        if !self.diagram.shapes.iter().any(|s| s.id == shape_id) {
+            return Err(JsValue::from_str("Shape not found"));
+        }
+        self.record(Op::UpdateShape {
+            shape_id: shape_id.to_string(),
+            fields,
+        });
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = deleteShape)]
+    pub fn delete_shape(&mut self, shape_id: &str) -> Result<(), JsValue> {
+        if !self.diagram.shapes.iter().any(|s| s.id == shape_id) {
+            return Err(JsValue::from_str("Shape not found"));
+        }
+        self.record(Op::DeleteShape {
+            shape_id: shape_id.to_string(),
+        });
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = addConnector)]
+    pub fn add_connector(&mut self, connector_json: &str) -> Result<(), JsValue> {
+        let connector: Connector = serde_json::from_str(connector_json)
+            .map_err(|e| JsValue::from_str(&format!("Parse error: {}", e)))?;
+        self.record(Op::AddConnector { connector });
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = deleteConnector)]
+    pub fn delete_connector(&mut self, connector_id: &str) -> Result<(), JsValue> {
+        if !self.diagram.connectors.iter().any(|c| c.id == connector_id) {
+            return Err(JsValue::from_str("Connector not found"));
+        }
+        self.record(Op::DeleteConnector {
+            connector_id: connector_id.to_string(),
+        });
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = updateSettings)]
+    pub fn update_settings(&mut self, settings_json: &str) -> Result<(), JsValue> {
+        let settings: DiagramSettings = serde_json::from_str(settings_json)
+            .map_err(|e| JsValue::from_str(&format!("Parse error: {}", e)))?;
+        self.record(Op::UpdateSettings { settings });
+        Ok(())
+    }
+
+    /// Return the current heads (hex SHA-256 hashes) of the change log
+    /// without recording anything new.
+    #[wasm_bindgen(js_name = getHeads)]
+    pub fn get_heads(&self) -> Vec<String> {
+        self.log.heads()
+    }
+
+    /// Alias for `getHeads`, named to match the "commit after a batch of
+    /// local edits" usage pattern: returns the heads a peer should be told
+    /// about so it can fetch everything new via `getChangesSince`.
+    #[wasm_bindgen(js_name = commit)]
+    pub fn commit(&mut self) -> Vec<String> {
+        self.log.heads()
+    }
+
+    /// Whether `shape_id` has been deleted (possibly by a remote peer),
+    /// i.e. it's tombstoned and any update referencing it will be dropped.
+    #[wasm_bindgen(js_name = isShapeDeleted)]
+    pub fn is_shape_deleted(&self, shape_id: &str) -> bool {
+        self.log.is_shape_deleted(shape_id)
+    }
+
+    /// Whether `connector_id` has been deleted (possibly by a remote peer).
+    #[wasm_bindgen(js_name = isConnectorDeleted)]
+    pub fn is_connector_deleted(&self, connector_id: &str) -> bool {
+        self.log.is_connector_deleted(connector_id)
+    }
+
+    /// Serialize every change not reachable from `heads` (a JSON array of
+    /// hex hashes) as a byte buffer suitable for sending to a peer.
+    #[wasm_bindgen(js_name = getChangesSince)]
+    pub fn get_changes_since(&self, heads: &str) -> Result<Vec<u8>, JsValue> {
+        let since: Vec<String> = serde_json::from_str(heads)
+            .map_err(|e| JsValue::from_str(&format!("Parse error: {}", e)))?;
+        let changes = self.log.changes_since(&since);
+        serde_json::to_vec(&changes)
+            .map_err(|e| JsValue::from_str(&format!("Serialize error: {}", e)))
+    }
+
+    /// Apply changes produced by `getChangesSince` on a peer to this
+    /// engine, merging them in dependency order.
+    #[wasm_bindgen(js_name = applyChanges)]
+    pub fn apply_changes(&mut self, bytes: Vec<u8>) -> Result<(), JsValue> {
+        let changes = serde_json::from_slice(&bytes)
+            .map_err(|e| JsValue::from_str(&format!("Parse error: {}", e)))?;
+        self.log.merge_changes(
+            changes,
+            &mut self.diagram.shapes,
+            &mut self.diagram.connectors,
+            &mut self.diagram.settings,
+        );
+        self.update_timestamp();
+        Ok(())
+    }
+
+    /// Merge another engine's change log into this one in-place.
+    #[wasm_bindgen(js_name = merge)]
+    pub fn merge(&mut self, other: &DiagramEngine) -> Result<(), JsValue> {
+        let changes = other.log.changes_since(&self.log.heads());
+        self.log.merge_changes(
+            changes,
+            &mut self.diagram.shapes,
+            &mut self.diagram.connectors,
+            &mut self.diagram.settings,
+        );
+        self.update_timestamp();
+        Ok(())
+    }
+
+    /// Record a local op: append it to the change log and apply it to the
+    /// diagram, then bump `updated_at`.
+    fn record(&mut self, op: Op) {
+        self.log.record_local(
+            op,
+            &mut self.diagram.shapes,
+            &mut self.diagram.connectors,
+            &mut self.diagram.settings,
+        );
+        self.update_timestamp();
+    }
+
+    #[wasm_bindgen(js_name = getShapes)]
+    pub fn get_shapes(&self) -> Result<String, JsValue> {
+        serde_json::to_string(&self.diagram.shapes)
+            .map_err(|e| JsValue::from_str(&format!("Serialize error: {}", e)))
+    }
+
+    #[wasm_bindgen(js_name = getConnectors)]
+    pub fn get_connectors(&self) -> Result<String, JsValue> {
+        serde_json::to_string(&self.diagram.connectors)
+            .map_err(|e| JsValue::from_str(&format!("Serialize error: {}", e)))
+    }
+
+    #[wasm_bindgen(js_name = getSettings)]
+    pub fn get_settings(&self) -> Result<String, JsValue> {
+        serde_json::to_string(&self.diagram.settings)
+            .map_err(|e| JsValue::from_str(&format!("Serialize error: {}", e)))
+    }
+
+    #[wasm_bindgen(js_name = snapToGrid)]
+    pub fn snap_to_grid(&self, x: f64, y: f64) -> Vec<f64> {
+        if self.diagram.settings.snap_to_grid {
+            let grid = self.diagram.settings.grid_size;
+            vec![
+                (x / grid).round() * grid,
+                (y / grid).round() * grid,
+            ]
+        } else {
+            vec![x, y]
+        }
+    }
+
+    /// Serialize the diagram as RDF triples in Turtle syntax, one full
+    /// triple per line (shapes as `:id a :Rectangle .`, `:id :x 100 .`, ...;
+    /// connectors as `:id :from :a .`, `:id :to :b .`).
+    #[wasm_bindgen(js_name = toTurtle)]
+    pub fn to_turtle(&self) -> String {
+        TripleStore::from_diagram(&self.diagram).to_turtle()
+    }
+
+    /// Run a SPARQL-subset query (basic graph patterns, `FILTER`, and the
+    /// `:from/:to+` property path over connectors) and return JSON
+    /// variable bindings.
+    #[wasm_bindgen(js_name = query)]
+    pub fn query(&self, sparql: &str) -> Result<String, JsValue> {
+        TripleStore::from_diagram(&self.diagram)
+            .query(sparql)
+            .map_err(|e| JsValue::from_str(&format!("Query error: {}", e)))
+    }
+
+    /// Reposition shapes using `algorithm` ("layered" for a Sugiyama-style
+    /// DAG layout, or "force-directed"), recording one `UpdateShape`
+    /// change per moved shape so the result merges like any other edit.
+    #[wasm_bindgen(js_name = autoLayout)]
+    pub fn auto_layout(&mut self, algorithm: &str) -> Result<(), JsValue> {
+        let mut positioned = self.diagram.shapes.clone();
+        layout::auto_layout(
+            algorithm,
+            &mut positioned,
+            &self.diagram.connectors,
+            &self.diagram.settings,
+        )
+        .map_err(|e| JsValue::from_str(&e))?;
+
+        let moves: Vec<(String, f64, f64)> = self
+            .diagram
+            .shapes
+            .iter()
+            .zip(positioned.iter())
+            .filter(|(old, new)| old.x != new.x || old.y != new.y)
+            .map(|(old, new)| (old.id.clone(), new.x, new.y))
+            .collect();
+
+        for (shape_id, x, y) in moves {
+            self.record(Op::UpdateShape {
+                shape_id,
+                fields: serde_json::json!({ "x": x, "y": y }),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Hit-test a point against the diagram, accounting for each shape's
+    /// `rotation` and its actual geometry (ellipse, rhombus or rectangle).
+    /// `tolerance` (in the same units as the diagram, default 0) grows
+    /// the shape's bounds so clicks near a thin stroke still register.
+    #[wasm_bindgen(js_name = findShapeAt)]
+    pub fn find_shape_at(&self, x: f64, y: f64, tolerance: Option<f64>) -> Option<String> {
+        let tolerance = tolerance.unwrap_or(0.0);
+        // Reverse iterate to find topmost shape
+        for shape in self.diagram.shapes.iter().rev() {
+            if shape_contains_point(shape, x, y, tolerance) {
+                return Some(shape.id.clone());
+            }
+        }
+        None
+    }
+
+    fn update_timestamp(&mut self) {
+        self.diagram.updated_at = js_sys::Date::new_0().to_iso_string().as_string().unwrap();
+    }
+}
+
+/// Test whether `(x, y)` falls inside `shape`, in the shape's own local
+/// frame: translate to the shape's center, then rotate by `-rotation` so
+/// the remaining test can assume an axis-aligned shape. `rotation` is in
+/// degrees, matching the CSS-style convention used elsewhere in the app.
+fn shape_contains_point(shape: &Shape, x: f64, y: f64, tolerance: f64) -> bool {
+    let center_x = shape.x + shape.width / 2.0;
+    let center_y = shape.y + shape.height / 2.0;
+    let angle = -shape.rotation.to_radians();
+    let (sin, cos) = angle.sin_cos();
+
+    let dx = x - center_x;
+    let dy = y - center_y;
+    let local_x = dx * cos - dy * sin;
+    let local_y = dx * sin + dy * cos;
+
+    let half_width = shape.width / 2.0 + tolerance;
+    let half_height = shape.height / 2.0 + tolerance;
+
+    match shape.shape_type {
+        ShapeType::Circle => {
+            if half_width <= 0.0 || half_height <= 0.0 {
+                return false;
+            }
+            (local_x / half_width).powi(2) + (local_y / half_height).powi(2) <= 1.0
+        }
+        ShapeType::Diamond => {
+            if half_width <= 0.0 || half_height <= 0.0 {
+                return false;
+            }
+            local_x.abs() / half_width + local_y.abs() / half_height <= 1.0
+        }
+        ShapeType::Rectangle | ShapeType::Text => {
+            local_x.abs() <= half_width && local_y.abs() <= half_height
+        }
+    }
+}
+
+// Utility functions
+#[wasm_bindgen(js_name = generateId)]
+pub fn generate_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+#[wasm_bindgen(js_name = createDefaultShape)]
+pub fn create_default_shape(shape_type: &str, x: f64, y: f64) -> Result<String, JsValue> {
+    let shape = Shape {
+        id: generate_id(),
+        shape_type: match shape_type {
+            "rectangle" => ShapeType::Rectangle,
+            "circle" => ShapeType::Circle,
+            "diamond" => ShapeType::Diamond,
+            "text" => ShapeType::Text,
+            _ => return Err(JsValue::from_str("Invalid shape type")),
+        },
+        x,
+        y,
+        width: 100.0,
+        height: 100.0,
+        rotation: 0.0,
+        fill: "#4f46e5".to_string(),
+        stroke: "#3730a3".to_string(),
+        stroke_width: 2.0,
+        text: if shape_type == "text" { Some("Text".to_string()) } else { None },
+    };
+
+    serde_json::to_string(&shape)
+        .map_err(|e| JsValue::from_str(&format!("Serialize error: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_diagram() {
+        let engine = DiagramEngine::new("test-id", "Test Diagram", None);
+        let json = engine.to_json().unwrap();
+        assert!(json.contains("test-id"));
+        assert!(json.contains("Test Diagram"));
+    }
+
+    #[test]
+    fn test_add_shape() {
+        let mut engine = DiagramEngine::new("test-id", "Test", None);
+        let shape = create_default_shape("rectangle", 100.0, 100.0).unwrap();
+        engine.add_shape(&shape).unwrap();
+        let shapes = engine.get_shapes().unwrap();
+        assert!(shapes.contains("rectangle"));
+    }
+
+    #[test]
+    fn test_snap_to_grid() {
+        let engine = DiagramEngine::new("test-id", "Test", None);
+        let snapped = engine.snap_to_grid(25.0, 33.0);
+        assert_eq!(snapped, vec![20.0, 40.0]);
+    }
+
+    #[test]
+    fn test_add_shape_advances_heads() {
+        let mut engine = DiagramEngine::new("test-id", "Test", Some("actor-a".to_string()));
+        assert!(engine.get_heads().is_empty());
+        let shape = create_default_shape("rectangle", 0.0, 0.0).unwrap();
+        engine.add_shape(&shape).unwrap();
+        assert_eq!(engine.get_heads().len(), 1);
+    }
+
+    #[test]
+    fn test_merge_converges_two_engines() {
+        let mut a = DiagramEngine::new("diagram", "Test", Some("actor-a".to_string()));
+        let shape = create_default_shape("rectangle", 0.0, 0.0).unwrap();
+        a.add_shape(&shape).unwrap();
+
+        let mut b = DiagramEngine::new("diagram", "Test", Some("actor-b".to_string()));
+        b.merge(&a).unwrap();
+
+        assert_eq!(a.get_shapes().unwrap(), b.get_shapes().unwrap());
+        assert_eq!(a.get_heads(), b.get_heads());
+    }
+
+    #[test]
+    fn test_delete_shape_marks_tombstone() {
+        let mut engine = DiagramEngine::new("test-id", "Test", None);
+        let shape: Shape = serde_json::from_str(&create_default_shape("rectangle", 0.0, 0.0).unwrap()).unwrap();
+        engine.add_shape(&serde_json::to_string(&shape).unwrap()).unwrap();
+
+        assert!(!engine.is_shape_deleted(&shape.id));
+        engine.delete_shape(&shape.id).unwrap();
+        assert!(engine.is_shape_deleted(&shape.id));
+    }
+
+    #[test]
+    fn test_to_turtle_and_query() {
+        let mut engine = DiagramEngine::new("test-id", "Test", None);
+        let shape = create_default_shape("rectangle", 0.0, 0.0).unwrap();
+        engine.add_shape(&shape).unwrap();
+
+        let turtle = engine.to_turtle();
+        assert!(turtle.contains("a :Rectangle"));
+
+        let result = engine.query("SELECT ?s WHERE { ?s :fill ?f . }").unwrap();
+        assert!(result.contains("\"vars\":[\"s\"]"));
+    }
+
+    #[test]
+    fn test_find_shape_at_respects_rotation() {
+        let mut engine = DiagramEngine::new("test-id", "Test", None);
+        let shape_json = create_default_shape("rectangle", 0.0, 0.0).unwrap();
+        let mut shape: Shape = serde_json::from_str(&shape_json).unwrap();
+        shape.rotation = 45.0;
+        engine
+            .add_shape(&serde_json::to_string(&shape).unwrap())
+            .unwrap();
+
+        // A corner of the unrotated bounding box is outside the diamond
+        // shape formed by rotating the square 45 degrees.
+        assert_eq!(engine.find_shape_at(2.0, 2.0, None), None);
+        // The (rotated) center is still inside.
+        assert_eq!(engine.find_shape_at(50.0, 50.0, None), Some(shape.id));
+    }
+
+    #[test]
+    fn test_find_shape_at_diamond_geometry() {
+        let mut engine = DiagramEngine::new("test-id", "Test", None);
+        let shape_json = create_default_shape("diamond", 0.0, 0.0).unwrap();
+        let shape: Shape = serde_json::from_str(&shape_json).unwrap();
+        engine.add_shape(&shape_json).unwrap();
+
+        // Corner of the bounding box is outside the rhombus.
+        assert_eq!(engine.find_shape_at(1.0, 1.0, None), None);
+        // Center is inside.
+        assert_eq!(engine.find_shape_at(50.0, 50.0, None), Some(shape.id));
+    }
+
+    #[test]
+    fn test_find_shape_at_tolerance_grows_hit_area() {
+        let mut engine = DiagramEngine::new("test-id", "Test", None);
+        let shape_json = create_default_shape("rectangle", 0.0, 0.0).unwrap();
+        let shape: Shape = serde_json::from_str(&shape_json).unwrap();
+        engine.add_shape(&shape_json).unwrap();
+
+        assert_eq!(engine.find_shape_at(105.0, 50.0, None), None);
+        assert_eq!(engine.find_shape_at(105.0, 50.0, Some(10.0)), Some(shape.id));
+    }
+
+    #[test]
+    fn test_auto_layout_moves_connected_shapes() {
+        let mut engine = DiagramEngine::new("test-id", "Test", None);
+        let a: Shape = serde_json::from_str(&create_default_shape("rectangle", 0.0, 0.0).unwrap()).unwrap();
+        let b: Shape = serde_json::from_str(&create_default_shape("rectangle", 0.0, 0.0).unwrap()).unwrap();
+        engine.add_shape(&serde_json::to_string(&a).unwrap()).unwrap();
+        engine.add_shape(&serde_json::to_string(&b).unwrap()).unwrap();
+
+        let connector = Connector {
+            id: "c1".to_string(),
+            from_shape_id: a.id.clone(),
+            to_shape_id: b.id.clone(),
+            from_anchor: "right".to_string(),
+            to_anchor: "left".to_string(),
+            stroke: "#000".to_string(),
+            stroke_width: 1.0,
+        };
+        engine
+            .add_connector(&serde_json::to_string(&connector).unwrap())
+            .unwrap();
+
+        engine.auto_layout("layered").unwrap();
+
+        let shapes: Vec<Shape> = serde_json::from_str(&engine.get_shapes().unwrap()).unwrap();
+        let by_id: std::collections::HashMap<&str, &Shape> =
+            shapes.iter().map(|s| (s.id.as_str(), s)).collect();
+        assert!(by_id[a.id.as_str()].y < by_id[b.id.as_str()].y);
+    }
+
+    #[test]
+    fn test_auto_layout_rejects_unknown_algorithm() {
+        let mut engine = DiagramEngine::new("test-id", "Test", None);
+        assert!(engine.auto_layout("spiral").is_err());
+    }
+}
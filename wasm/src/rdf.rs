@@ -0,0 +1,625 @@
+// RDF view over a diagram, plus a small SPARQL-subset evaluator.
+//
+// The diagram is modelled as a set of `(subject, predicate, object)`
+// triples: each shape becomes `:id a :ShapeType` plus one triple per
+// scalar field, and each connector becomes `:id a :Connector` plus
+// `:id :from :shapeA` / `:id :to :shapeB`. This is enough to answer graph-shaped questions
+// (reachability, cycles, simple validation) without reimplementing graph
+// traversal on the JS side.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::{Connector, Diagram, Shape, ShapeType};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Term {
+    Iri(String),
+    Literal(String),
+    Number(f64),
+    Var(String),
+}
+
+impl Term {
+    fn is_var(&self) -> bool {
+        matches!(self, Term::Var(_))
+    }
+
+    fn to_turtle(&self) -> String {
+        match self {
+            Term::Iri(iri) => format!(":{}", iri),
+            Term::Literal(s) => format!("{:?}", s),
+            Term::Number(n) => format!("{}", n),
+            Term::Var(v) => format!("?{}", v),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Triple {
+    pub subject: Term,
+    pub predicate: String,
+    pub object: Term,
+}
+
+/// An in-memory triple store built from a `Diagram`.
+pub struct TripleStore {
+    triples: Vec<Triple>,
+}
+
+fn shape_type_name(shape_type: &ShapeType) -> &'static str {
+    match shape_type {
+        ShapeType::Rectangle => "Rectangle",
+        ShapeType::Circle => "Circle",
+        ShapeType::Diamond => "Diamond",
+        ShapeType::Text => "Text",
+    }
+}
+
+impl TripleStore {
+    pub fn from_diagram(diagram: &Diagram) -> Self {
+        let mut triples = Vec::new();
+        for shape in &diagram.shapes {
+            push_shape_triples(&mut triples, shape);
+        }
+        for connector in &diagram.connectors {
+            push_connector_triples(&mut triples, connector);
+        }
+        TripleStore { triples }
+    }
+
+    pub fn to_turtle(&self) -> String {
+        let mut lines = Vec::with_capacity(self.triples.len());
+        for triple in &self.triples {
+            // `a` is Turtle's keyword shorthand for `rdf:type` and must
+            // never be prefixed like a regular predicate IRI.
+            let predicate = if triple.predicate == "a" {
+                "a".to_string()
+            } else {
+                format!(":{}", triple.predicate)
+            };
+            lines.push(format!(
+                "{} {} {} .",
+                triple.subject.to_turtle(),
+                predicate,
+                triple.object.to_turtle()
+            ));
+        }
+        lines.join("\n")
+    }
+
+    /// Evaluate a practical SPARQL subset: a `SELECT ?a ?b WHERE { ... }`
+    /// block of triple patterns and `FILTER` comparisons, plus the
+    /// `:from/:to+` property path for transitive connector traversal.
+    /// Returns JSON `{ "vars": [...], "bindings": [...] }`.
+    pub fn query(&self, sparql: &str) -> Result<String, String> {
+        let parsed = parse_query(sparql)?;
+        let mut bindings = vec![Bindings::new()];
+
+        // Order patterns so the most-constrained variable is bound first:
+        // patterns with fewer unbound variables go first, which keeps
+        // each join narrow instead of cross-producting loosely bound
+        // patterns.
+        let mut patterns = parsed.patterns.clone();
+        patterns.sort_by_key(pattern_unbound_count);
+
+        for pattern in &patterns {
+            bindings = match pattern {
+                Pattern::Triple(tp) => self.join_triple_pattern(&bindings, tp),
+                Pattern::TransitivePath { subject, object } => {
+                    self.join_transitive_from_to(&bindings, subject, object)
+                }
+            };
+            if bindings.is_empty() {
+                break;
+            }
+        }
+
+        for filter in &parsed.filters {
+            bindings.retain(|b| eval_filter(filter, b));
+        }
+
+        let vars = parsed.select_vars.clone();
+        let rows: Vec<HashMap<String, serde_json::Value>> = bindings
+            .iter()
+            .map(|b| {
+                vars.iter()
+                    .map(|v| (v.clone(), term_to_json(b.get(v))))
+                    .collect()
+            })
+            .collect();
+
+        let result = serde_json::json!({ "vars": vars, "bindings": rows });
+        serde_json::to_string(&result).map_err(|e| e.to_string())
+    }
+
+    fn join_triple_pattern(&self, bindings: &[Bindings], pattern: &TriplePattern) -> Vec<Bindings> {
+        let mut out = Vec::new();
+        for binding in bindings {
+            for triple in &self.triples {
+                if pattern.predicate != triple.predicate {
+                    continue;
+                }
+                let mut candidate = binding.clone();
+                if unify(&mut candidate, &pattern.subject, &triple.subject)
+                    && unify(&mut candidate, &pattern.object, &triple.object)
+                {
+                    out.push(candidate);
+                }
+            }
+        }
+        out
+    }
+
+    /// `?subject :from/:to+ ?object`: every shape reachable from
+    /// `?subject` by following one or more connectors, computed with a
+    /// BFS per starting shape so cycles terminate.
+    fn join_transitive_from_to(
+        &self,
+        bindings: &[Bindings],
+        subject: &Term,
+        object: &Term,
+    ) -> Vec<Bindings> {
+        let edges = self.connector_edges();
+        let mut out = Vec::new();
+        for binding in bindings {
+            let starts: Vec<String> = match subject {
+                Term::Var(name) => match binding.get(name) {
+                    Some(Term::Iri(iri)) => vec![iri.clone()],
+                    _ => edges.keys().cloned().collect(),
+                },
+                Term::Iri(iri) => vec![iri.clone()],
+                _ => continue,
+            };
+
+            for start in starts {
+                for reached in reachable_from(&edges, &start) {
+                    let mut candidate = binding.clone();
+                    if unify(&mut candidate, subject, &Term::Iri(start.clone()))
+                        && unify(&mut candidate, object, &Term::Iri(reached))
+                    {
+                        out.push(candidate);
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    fn connector_edges(&self) -> HashMap<String, Vec<String>> {
+        let mut edges: HashMap<String, Vec<String>> = HashMap::new();
+        let mut i = 0;
+        while i < self.triples.len() {
+            let from_triple = &self.triples[i];
+            if from_triple.predicate == "from" {
+                if let (Term::Iri(conn), Term::Iri(from_shape)) =
+                    (&from_triple.subject, &from_triple.object)
+                {
+                    if let Some(to_shape) = self.triples.iter().find_map(|t| {
+                        if t.predicate == "to" && t.subject == Term::Iri(conn.clone()) {
+                            if let Term::Iri(to) = &t.object {
+                                return Some(to.clone());
+                            }
+                        }
+                        None
+                    }) {
+                        edges.entry(from_shape.clone()).or_default().push(to_shape);
+                    }
+                }
+            }
+            i += 1;
+        }
+        edges
+    }
+}
+
+fn reachable_from(edges: &HashMap<String, Vec<String>>, start: &str) -> HashSet<String> {
+    let mut seen = HashSet::new();
+    let mut queue: VecDeque<String> = edges
+        .get(start)
+        .cloned()
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    while let Some(node) = queue.pop_front() {
+        if seen.insert(node.clone()) {
+            if let Some(next) = edges.get(&node) {
+                queue.extend(next.iter().cloned());
+            }
+        }
+    }
+    seen
+}
+
+fn push_shape_triples(triples: &mut Vec<Triple>, shape: &Shape) {
+    let subject = Term::Iri(shape.id.clone());
+    triples.push(Triple {
+        subject: subject.clone(),
+        predicate: "a".to_string(),
+        object: Term::Iri(shape_type_name(&shape.shape_type).to_string()),
+    });
+    triples.push(Triple { subject: subject.clone(), predicate: "x".to_string(), object: Term::Number(shape.x) });
+    triples.push(Triple { subject: subject.clone(), predicate: "y".to_string(), object: Term::Number(shape.y) });
+    triples.push(Triple { subject: subject.clone(), predicate: "width".to_string(), object: Term::Number(shape.width) });
+    triples.push(Triple { subject: subject.clone(), predicate: "height".to_string(), object: Term::Number(shape.height) });
+    triples.push(Triple { subject: subject.clone(), predicate: "rotation".to_string(), object: Term::Number(shape.rotation) });
+    triples.push(Triple { subject: subject.clone(), predicate: "fill".to_string(), object: Term::Literal(shape.fill.clone()) });
+    triples.push(Triple { subject: subject.clone(), predicate: "stroke".to_string(), object: Term::Literal(shape.stroke.clone()) });
+    triples.push(Triple { subject: subject.clone(), predicate: "strokeWidth".to_string(), object: Term::Number(shape.stroke_width) });
+    if let Some(text) = &shape.text {
+        triples.push(Triple { subject, predicate: "text".to_string(), object: Term::Literal(text.clone()) });
+    }
+}
+
+fn push_connector_triples(triples: &mut Vec<Triple>, connector: &Connector) {
+    let subject = Term::Iri(connector.id.clone());
+    triples.push(Triple {
+        subject: subject.clone(),
+        predicate: "a".to_string(),
+        object: Term::Iri("Connector".to_string()),
+    });
+    triples.push(Triple {
+        subject: subject.clone(),
+        predicate: "from".to_string(),
+        object: Term::Iri(connector.from_shape_id.clone()),
+    });
+    triples.push(Triple {
+        subject,
+        predicate: "to".to_string(),
+        object: Term::Iri(connector.to_shape_id.clone()),
+    });
+}
+
+#[derive(Clone, Debug)]
+struct TriplePattern {
+    subject: Term,
+    predicate: String,
+    object: Term,
+}
+
+#[derive(Clone, Debug)]
+enum Pattern {
+    Triple(TriplePattern),
+    TransitivePath { subject: Term, object: Term },
+}
+
+fn pattern_unbound_count(pattern: &Pattern) -> usize {
+    match pattern {
+        Pattern::Triple(tp) => tp.subject.is_var() as usize + tp.object.is_var() as usize,
+        Pattern::TransitivePath { subject, object } => {
+            subject.is_var() as usize + object.is_var() as usize
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum FilterOp {
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+}
+
+#[derive(Clone, Debug)]
+struct Filter {
+    var: String,
+    op: FilterOp,
+    value: Term,
+}
+
+struct ParsedQuery {
+    select_vars: Vec<String>,
+    patterns: Vec<Pattern>,
+    filters: Vec<Filter>,
+}
+
+#[derive(Clone, Default)]
+struct Bindings(HashMap<String, Term>);
+
+impl Bindings {
+    fn new() -> Self {
+        Bindings(HashMap::new())
+    }
+
+    fn get(&self, var: &str) -> Option<&Term> {
+        self.0.get(var)
+    }
+
+    fn insert(&mut self, var: String, term: Term) {
+        self.0.insert(var, term);
+    }
+}
+
+fn unify(bindings: &mut Bindings, pattern_term: &Term, value: &Term) -> bool {
+    match pattern_term {
+        Term::Var(name) => match bindings.get(name) {
+            Some(existing) => existing == value,
+            None => {
+                bindings.insert(name.clone(), value.clone());
+                true
+            }
+        },
+        other => other == value,
+    }
+}
+
+fn term_to_json(term: Option<&Term>) -> serde_json::Value {
+    match term {
+        Some(Term::Iri(iri)) => serde_json::json!({ "type": "uri", "value": iri }),
+        Some(Term::Literal(s)) => serde_json::json!({ "type": "literal", "value": s }),
+        Some(Term::Number(n)) => serde_json::json!({ "type": "literal", "value": n }),
+        Some(Term::Var(_)) | None => serde_json::Value::Null,
+    }
+}
+
+fn eval_filter(filter: &Filter, bindings: &Bindings) -> bool {
+    let bound = match bindings.get(&filter.var) {
+        Some(t) => t,
+        None => return false,
+    };
+    match (bound, &filter.value) {
+        (Term::Number(a), Term::Number(b)) => compare(*a, *b, &filter.op),
+        (Term::Literal(a), Term::Literal(b)) => compare_str(a, b, &filter.op),
+        (Term::Iri(a), Term::Iri(b)) => compare_str(a, b, &filter.op),
+        _ => false,
+    }
+}
+
+fn compare(a: f64, b: f64, op: &FilterOp) -> bool {
+    match op {
+        FilterOp::Eq => a == b,
+        FilterOp::Ne => a != b,
+        FilterOp::Lt => a < b,
+        FilterOp::Gt => a > b,
+        FilterOp::Le => a <= b,
+        FilterOp::Ge => a >= b,
+    }
+}
+
+fn compare_str(a: &str, b: &str, op: &FilterOp) -> bool {
+    match op {
+        FilterOp::Eq => a == b,
+        FilterOp::Ne => a != b,
+        FilterOp::Lt => a < b,
+        FilterOp::Gt => a > b,
+        FilterOp::Le => a <= b,
+        FilterOp::Ge => a >= b,
+    }
+}
+
+/// Parse a query of the shape:
+/// `SELECT ?a ?b WHERE { ?a :from/:to+ ?b . ?a :fill ?f . FILTER(?f = "#fff") }`
+fn parse_query(sparql: &str) -> Result<ParsedQuery, String> {
+    let trimmed = sparql.trim();
+    let upper = trimmed.to_uppercase();
+    let select_pos = upper.find("SELECT").ok_or("expected SELECT")?;
+    let where_pos = upper.find("WHERE").ok_or("expected WHERE")?;
+    let select_vars: Vec<String> = trimmed[select_pos + "SELECT".len()..where_pos]
+        .split_whitespace()
+        .map(|v| v.trim_start_matches('?').to_string())
+        .collect();
+
+    let open = trimmed.find('{').ok_or("expected {")?;
+    let close = trimmed.rfind('}').ok_or("expected }")?;
+    let body = &trimmed[open + 1..close];
+
+    let mut patterns = Vec::new();
+    let mut filters = Vec::new();
+    for clause in split_clauses(body) {
+        let clause = clause.trim();
+        if clause.is_empty() {
+            continue;
+        }
+        if clause.to_uppercase().starts_with("FILTER") {
+            filters.push(parse_filter(clause)?);
+            continue;
+        }
+        patterns.push(parse_pattern(clause)?);
+    }
+
+    Ok(ParsedQuery { select_vars, patterns, filters })
+}
+
+/// Split a `WHERE` body into clauses on `.`, treating a `.` as a clause
+/// terminator only when it isn't part of a decimal number (digit on both
+/// sides, e.g. `5.5`) or inside a quoted string literal (e.g. `"Hello."`).
+fn split_clauses(body: &str) -> Vec<String> {
+    let chars: Vec<char> = body.chars().collect();
+    let mut clauses = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+
+    for i in 0..chars.len() {
+        let c = chars[i];
+        if c == '"' {
+            in_quotes = !in_quotes;
+            current.push(c);
+            continue;
+        }
+        if c == '.' && !in_quotes {
+            let prev_is_digit = i > 0 && chars[i - 1].is_ascii_digit();
+            let next_is_digit = i + 1 < chars.len() && chars[i + 1].is_ascii_digit();
+            if !(prev_is_digit && next_is_digit) {
+                clauses.push(current.clone());
+                current.clear();
+                continue;
+            }
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        clauses.push(current);
+    }
+    clauses
+}
+
+fn parse_term(token: &str) -> Term {
+    let token = token.trim();
+    if let Some(var) = token.strip_prefix('?') {
+        Term::Var(var.to_string())
+    } else if let Some(iri) = token.strip_prefix(':') {
+        Term::Iri(iri.to_string())
+    } else if let Ok(n) = token.parse::<f64>() {
+        Term::Number(n)
+    } else {
+        Term::Literal(token.trim_matches('"').to_string())
+    }
+}
+
+fn parse_pattern(clause: &str) -> Result<Pattern, String> {
+    let parts: Vec<&str> = clause.split_whitespace().collect();
+    if parts.len() != 3 {
+        return Err(format!("malformed triple pattern: {}", clause));
+    }
+    let subject = parse_term(parts[0]);
+    let object = parse_term(parts[2]);
+
+    if parts[1] == ":from/:to+" {
+        return Ok(Pattern::TransitivePath { subject, object });
+    }
+
+    let predicate = parts[1]
+        .strip_prefix(':')
+        .ok_or_else(|| format!("expected predicate IRI: {}", parts[1]))?
+        .to_string();
+    Ok(Pattern::Triple(TriplePattern { subject, predicate, object }))
+}
+
+fn parse_filter(clause: &str) -> Result<Filter, String> {
+    let inner_start = clause.find('(').ok_or("malformed FILTER")?;
+    let inner_end = clause.rfind(')').ok_or("malformed FILTER")?;
+    let inner = &clause[inner_start + 1..inner_end];
+
+    for (token, op) in [
+        ("!=", FilterOp::Ne),
+        (">=", FilterOp::Ge),
+        ("<=", FilterOp::Le),
+        ("=", FilterOp::Eq),
+        ("<", FilterOp::Lt),
+        (">", FilterOp::Gt),
+    ] {
+        if let Some(pos) = inner.find(token) {
+            let var = inner[..pos].trim().trim_start_matches('?').to_string();
+            let value = parse_term(inner[pos + token.len()..].trim());
+            return Ok(Filter { var, op, value });
+        }
+    }
+    Err(format!("unsupported FILTER expression: {}", clause))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DiagramSettings;
+
+    fn diagram_with_chain() -> Diagram {
+        let shape = |id: &str| Shape {
+            id: id.to_string(),
+            shape_type: ShapeType::Rectangle,
+            x: 0.0,
+            y: 0.0,
+            width: 10.0,
+            height: 10.0,
+            rotation: 0.0,
+            fill: "#fff".to_string(),
+            stroke: "#000".to_string(),
+            stroke_width: 1.0,
+            text: None,
+        };
+        Diagram {
+            id: "d".to_string(),
+            name: "Chain".to_string(),
+            shapes: vec![shape("a"), shape("b"), shape("c")],
+            connectors: vec![
+                Connector {
+                    id: "c1".to_string(),
+                    from_shape_id: "a".to_string(),
+                    to_shape_id: "b".to_string(),
+                    from_anchor: "right".to_string(),
+                    to_anchor: "left".to_string(),
+                    stroke: "#000".to_string(),
+                    stroke_width: 1.0,
+                },
+                Connector {
+                    id: "c2".to_string(),
+                    from_shape_id: "b".to_string(),
+                    to_shape_id: "c".to_string(),
+                    from_anchor: "right".to_string(),
+                    to_anchor: "left".to_string(),
+                    stroke: "#000".to_string(),
+                    stroke_width: 1.0,
+                },
+            ],
+            settings: DiagramSettings::default(),
+            created_at: "now".to_string(),
+            updated_at: "now".to_string(),
+        }
+    }
+
+    #[test]
+    fn to_turtle_contains_shape_and_connector_triples() {
+        let store = TripleStore::from_diagram(&diagram_with_chain());
+        let turtle = store.to_turtle();
+        assert!(turtle.contains(":a a :Rectangle ."));
+        assert!(turtle.contains(":c1 :from :a ."));
+        assert!(turtle.contains(":c1 :to :b ."));
+    }
+
+    #[test]
+    fn to_turtle_includes_stroke_fields() {
+        let store = TripleStore::from_diagram(&diagram_with_chain());
+        let turtle = store.to_turtle();
+        assert!(turtle.contains(":a :stroke \"#000\" ."));
+        assert!(turtle.contains(":a :strokeWidth 1 ."));
+    }
+
+    #[test]
+    fn filter_handles_decimal_numbers() {
+        let store = TripleStore::from_diagram(&diagram_with_chain());
+        let result = store
+            .query("SELECT ?s WHERE { ?s :width ?w . FILTER(?w > 5.5) }")
+            .unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&result).unwrap();
+        assert_eq!(parsed["bindings"].as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn filter_handles_string_literal_with_period() {
+        let store = TripleStore::from_diagram(&diagram_with_chain());
+        let result = store
+            .query("SELECT ?s WHERE { ?s :fill ?f . FILTER(?f != \"Hello.\") }")
+            .unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&result).unwrap();
+        assert_eq!(parsed["bindings"].as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn transitive_path_finds_reachable_shapes() {
+        let store = TripleStore::from_diagram(&diagram_with_chain());
+        let result = store
+            .query("SELECT ?to WHERE { :a :from/:to+ ?to . }")
+            .unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&result).unwrap();
+        let bindings = parsed["bindings"].as_array().unwrap();
+        let reached: HashSet<String> = bindings
+            .iter()
+            .map(|b| b["to"]["value"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(reached, HashSet::from(["b".to_string(), "c".to_string()]));
+    }
+
+    #[test]
+    fn filter_narrows_bindings_by_field() {
+        let mut diagram = diagram_with_chain();
+        diagram.shapes[0].width = 4.0;
+        let store = TripleStore::from_diagram(&diagram);
+        let result = store
+            .query("SELECT ?s WHERE { ?s :width ?w . FILTER(?w > 5) }")
+            .unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&result).unwrap();
+        let bindings = parsed["bindings"].as_array().unwrap();
+        assert_eq!(bindings.len(), 2);
+        assert!(bindings.iter().all(|b| b["s"]["value"] != "a"));
+    }
+}